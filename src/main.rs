@@ -7,16 +7,18 @@ extern crate lazy_static;
 #[macro_use]
 extern crate quick_error;
 
-use futures_async_stream::{try_stream, for_await};
-use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use futures_async_stream::try_stream;
+use serde::{Deserialize, Serialize};
 
 quick_error! {
     #[derive(Debug)]
     pub enum Error {
         Io(err: std::io::Error) { from() }
-        PathPersist(err: tempfile::PathPersistError) { from() }
-        Persist(err: tempfile::PersistError) { from() }
         Reqwest(err: reqwest::Error) { from() }
+        Json(err: serde_json::Error) { from() }
     }
 }
 
@@ -31,13 +33,44 @@ struct Issue {
 
 lazy_static! {
     static ref CODEBLOCK_REGEXP: regex::Regex =
-        regex::Regex::new(r"```rust(?P<snippet>[^`]+)```").unwrap();
+        regex::Regex::new(r"```(?:rust|rs)(?P<snippet>[^`]+)```").unwrap();
+    // rustc's own test-suite convention for annotating a reproducer inline:
+    // `// compile-flags: --edition 2021 -Zfoo` and `// edition:2021`.
+    static ref COMPILE_FLAGS_REGEXP: regex::Regex =
+        regex::Regex::new(r"(?m)^\s*//\s*compile-flags:\s*(?P<flags>.+)$").unwrap();
+    static ref EDITION_REGEXP: regex::Regex =
+        regex::Regex::new(r"(?m)^\s*//\s*edition:\s*(?P<edition>\S+)$").unwrap();
+}
+
+#[derive(Clone, Debug)]
+struct Mcve {
+    source: String,
+    extra_flags: Vec<String>,
+    edition: Option<String>,
+}
+
+fn parse_directives(source: &str) -> (Vec<String>, Option<String>) {
+    let mut extra_flags = Vec::new();
+    for capture in COMPILE_FLAGS_REGEXP.captures_iter(source) {
+        extra_flags.extend(capture["flags"].split_whitespace().map(str::to_owned));
+    }
+    let edition = EDITION_REGEXP
+        .captures(source)
+        .map(|c| c["edition"].to_owned());
+
+    (extra_flags, edition)
 }
 
-fn get_mcves<'i>(issue: &'i Issue) -> impl Iterator<Item = String> + 'i {
-    CODEBLOCK_REGEXP
-        .captures_iter(&issue.body)
-        .map(|c| c["snippet"].trim().to_owned())
+fn get_mcves<'i>(issue: &'i Issue) -> impl Iterator<Item = Mcve> + 'i {
+    CODEBLOCK_REGEXP.captures_iter(&issue.body).map(|c| {
+        let source = c["snippet"].trim().to_owned();
+        let (extra_flags, edition) = parse_directives(&source);
+        Mcve {
+            source,
+            extra_flags,
+            edition,
+        }
+    })
 }
 
 fn get_next_link(response: &reqwest::Response) -> Option<String> {
@@ -69,21 +102,90 @@ fn get_next_link(response: &reqwest::Response) -> Option<String> {
         .map(|(_, url)| url.to_owned())
 }
 
+lazy_static! {
+    static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::builder()
+        .user_agent("rust-ices-triage-scan")
+        .build()
+        .expect("failed to build the GitHub API client");
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct CachedPage {
+    etag: String,
+    body: String,
+    next_url: Option<String>,
+}
+
+type EtagCache = std::collections::HashMap<String, CachedPage>;
+
+const ETAG_CACHE_PATH: &str = "ices-scan-etag-cache.json";
+
+fn load_etag_cache() -> EtagCache {
+    std::fs::read_to_string(ETAG_CACHE_PATH)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_etag_cache(cache: &EtagCache) -> Result<(), Error> {
+    let contents =
+        serde_json::to_string_pretty(cache).expect("an EtagCache value is always serializable");
+    std::fs::write(ETAG_CACHE_PATH, contents)?;
+    Ok(())
+}
+
 #[try_stream(ok = Issue, error = Error)]
-async fn get_issues() {
+async fn get_issues(etag_cache: &mut EtagCache) {
     let mut next_url = Some(ISSUES_URL.to_owned());
 
     while let Some(url) = next_url {
-        let response = reqwest::get(&url).await?;
-        next_url = get_next_link(&response);
-        let issues: Vec<Issue> = response.json().await?;
+        let mut request = HTTP_CLIENT.get(&url);
+        if let Some(cached) = etag_cache.get(&url) {
+            request = request.header(reqwest::header::IF_NONE_MATCH, &cached.etag);
+        }
+        let response = request.send().await?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            let cached = etag_cache
+                .get(&url)
+                .cloned()
+                .expect("a 304 implies we sent an If-None-Match from our own cache");
+            next_url = cached.next_url;
+            let issues: Vec<Issue> = serde_json::from_str(&cached.body)?;
+            for issue in issues {
+                yield issue;
+            }
+            continue;
+        }
+
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let page_next_url = get_next_link(&response);
+        let body = response.text().await?;
+
+        if let Some(etag) = etag {
+            etag_cache.insert(
+                url.clone(),
+                CachedPage {
+                    etag,
+                    body: body.clone(),
+                    next_url: page_next_url.clone(),
+                },
+            );
+        }
+
+        next_url = page_next_url;
+        let issues: Vec<Issue> = serde_json::from_str(&body)?;
         for issue in issues {
             yield issue;
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum CompilationResult {
     ICE,
     Failed,
@@ -101,57 +203,242 @@ impl std::fmt::Display for CompilationResult {
     }
 }
 
-async fn run_test(toolchain: &str, input: &str) -> Result<CompilationResult, Error> {
-    use tokio::process::Command;
+impl CompilationResult {
+    fn severity(&self) -> u8 {
+        match self {
+            Self::Compiled => 0,
+            Self::Failed => 1,
+            Self::ICE => 2,
+        }
+    }
+}
+
+lazy_static! {
+    // rustc ICEs embed an absolute sysroot path like `/rustc/<40-hex-char
+    // commit hash>/library/core/src/panic.rs`; the hash and the source path
+    // under it both vary per toolchain and aren't interesting for a diff.
+    static ref RUSTC_SYSROOT_REGEXP: regex::Regex =
+        regex::Regex::new(r"/rustc/[0-9a-f]{40}[^\s:]*").unwrap();
+    static ref LINE_COL_REGEXP: regex::Regex = regex::Regex::new(r":\d+:\d+").unwrap();
+    // Current rustc (1.65+): `thread 'rustc' (1234) panicked at $DIR/foo.rs:\n<message>`
+    // (the `:line:col` is already gone by the time LINE_COL_REGEXP has run).
+    static ref PANIC_MESSAGE_REGEXP: regex::Regex =
+        regex::Regex::new(r"thread '[^']*'(?: \(\d+\))? panicked at [^\n]*:\n(?P<message>[^\n]*)")
+            .unwrap();
+    // Pre-1.65: `thread 'rustc' panicked at 'message', $DIR/foo.rs:line:col`.
+    static ref PANIC_MESSAGE_LEGACY_REGEXP: regex::Regex =
+        regex::Regex::new(r#"thread '[^']*' panicked at '(?P<message>.*?)'"#).unwrap();
+    static ref ERROR_CODE_REGEXP: regex::Regex = regex::Regex::new(r"error\[(E\d+)\]").unwrap();
+}
+
+fn normalize_rustc_output(
+    raw: &str,
+    source_path: &std::path::Path,
+    artifact_path: &std::path::Path,
+) -> String {
+    let source_path = source_path.display().to_string();
+    let artifact_path = artifact_path.display().to_string();
+
+    let mut normalized = raw.replace(source_path.as_str(), "$DIR");
+    normalized = normalized.replace(artifact_path.as_str(), "$OUT");
+    normalized = RUSTC_SYSROOT_REGEXP
+        .replace_all(&normalized, "$$RUSTC")
+        .into_owned();
+    normalized = LINE_COL_REGEXP.replace_all(&normalized, "").into_owned();
+
+    let mut in_backtrace = false;
+    let mut lines = Vec::new();
+    for line in normalized.lines() {
+        if line.starts_with("stack backtrace:") {
+            in_backtrace = true;
+            lines.push("stack backtrace: (omitted)".to_owned());
+            continue;
+        }
+        if in_backtrace {
+            continue;
+        }
+        if line.starts_with("note: rustc ") && line.contains("running on") {
+            continue;
+        }
+        if line.starts_with("query stack during panic:") || line.trim_start().starts_with('#') {
+            continue;
+        }
+        lines.push(line.to_owned());
+    }
+    lines.join("\n")
+}
+
+fn extract_panic_message(normalized: &str) -> Option<String> {
+    PANIC_MESSAGE_REGEXP
+        .captures(normalized)
+        .or_else(|| PANIC_MESSAGE_LEGACY_REGEXP.captures(normalized))
+        .map(|c| c["message"].to_owned())
+}
+
+fn extract_error_code(normalized: &str) -> Option<String> {
+    ERROR_CODE_REGEXP
+        .captures(normalized)
+        .map(|c| c[1].to_owned())
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct TestOutcome {
+    classification: CompilationResult,
+    panic_message: Option<String>,
+    error_code: Option<String>,
+    normalized_output: String,
+}
 
-    let (stdin, stdin_path) = tempfile::NamedTempFile::new()?.keep()?;
-    std::fs::write(&stdin_path, input)?;
+async fn run_test(toolchain: &str, mcve: &Mcve) -> Result<TestOutcome, Error> {
+    let mut source_file = tempfile::Builder::new().suffix(".rs").tempfile()?;
+    source_file.write_all(mcve.source.as_bytes())?;
 
-    let artifact_path = tempfile::NamedTempFile::new()?.into_temp_path().keep()?;
+    let artifact_file = tempfile::NamedTempFile::new()?;
 
-    let (stdout, stdout_path) = tempfile::NamedTempFile::new()?.keep()?;
-    let stderr = stdout.try_clone()?;
+    let stdout_file = tempfile::NamedTempFile::new()?;
+    let stdout = stdout_file.reopen()?;
+    let stderr = stdout_file.reopen()?;
 
-    let output = Command::new("rustup")
+    let acquired = tokio::task::spawn_blocking(|| JOBSERVER.acquire())
+        .await
+        .unwrap()?;
+
+    let mut command = std::process::Command::new("rustup");
+    command
         .arg("run")
         .arg(toolchain)
         .arg("rustc")
-        .arg("-")
+        .arg(source_file.path())
         .arg("-o")
-        .arg(&artifact_path)
-        .stdin(stdin)
+        .arg(artifact_file.path());
+    if let Some(edition) = &mcve.edition {
+        command.arg("--edition").arg(edition);
+    }
+    command
+        .args(&mcve.extra_flags)
         .stdout(stdout)
-        .stderr(stderr)
+        .stderr(stderr);
+    JOBSERVER.configure(&mut command);
+
+    let output = tokio::process::Command::from(command)
         .spawn()?
         .wait_with_output()
         .await?;
 
-    let result = if output.status.success() {
+    drop(acquired);
+
+    let raw_output = std::fs::read_to_string(stdout_file.path())?;
+    let normalized_output =
+        normalize_rustc_output(&raw_output, source_file.path(), artifact_file.path());
+
+    let classification = if output.status.success() {
         CompilationResult::Compiled
+    } else if raw_output.contains("internal compiler error") {
+        CompilationResult::ICE
     } else {
-        let buffer = std::fs::read_to_string(&stdout_path)?;
-        if buffer.contains("internal compiler error") {
-            CompilationResult::ICE
-        } else {
-            CompilationResult::Failed
-        }
+        CompilationResult::Failed
     };
 
-    Ok(result)
+    Ok(TestOutcome {
+        panic_message: extract_panic_message(&normalized_output),
+        error_code: extract_error_code(&normalized_output),
+        classification,
+        normalized_output,
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputMode {
+    Table,
+    Json,
+}
+
+struct Args {
+    jobs: Option<usize>,
+    output: OutputMode,
+    watch: Option<u64>,
+    toolchains: Vec<String>,
+}
+
+impl Args {
+    fn parse() -> Args {
+        let mut jobs = None;
+        let mut output = OutputMode::Table;
+        let mut watch = None;
+        let mut toolchains = Vec::new();
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-j" | "--jobs" => {
+                    let value = args.next().expect("-j/--jobs requires a value");
+                    jobs = Some(value.parse().expect("-j/--jobs expects an integer"));
+                }
+                "--output" => {
+                    let value = args.next().expect("--output requires a value");
+                    output = match value.as_str() {
+                        "table" => OutputMode::Table,
+                        "json" => OutputMode::Json,
+                        other => panic!("unknown --output mode: {}", other),
+                    };
+                }
+                "--watch" => {
+                    let value = args
+                        .next()
+                        .expect("--watch requires an interval in seconds");
+                    watch = Some(
+                        value
+                            .parse()
+                            .expect("--watch expects an integer number of seconds"),
+                    );
+                }
+                _ => toolchains.push(format!("nightly-{}", arg)),
+            }
+        }
+
+        Args {
+            jobs,
+            output,
+            watch,
+            toolchains,
+        }
+    }
 }
 
 lazy_static! {
-    static ref TOOLCHAINS: Vec<String> = std::env::args()
-        .skip(1)
-        .map(|name| format!("nightly-{}", name))
-        .collect();
+    static ref ARGS: Args = Args::parse();
+    static ref TOOLCHAINS: &'static Vec<String> = &ARGS.toolchains;
+}
+
+lazy_static! {
+    static ref ISSUE_CONCURRENCY: usize = std::env::var("ICE_SCAN_CONCURRENCY")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(8);
+}
+
+lazy_static! {
+    static ref JOBSERVER: jobserver::Client = {
+        // Safety: we trust that a jobserver fd pair inherited via
+        // `--jobserver-auth`/`MAKEFLAGS` refers to an actual jobserver pipe
+        // handed to us by the parent `cargo`/`make`, per the contract
+        // documented on `Client::from_env`.
+        unsafe { jobserver::Client::from_env() }.unwrap_or_else(|| {
+            jobserver::Client::new(ARGS.jobs.unwrap_or_else(num_cpus::get))
+                .expect("failed to create jobserver client")
+        })
+    };
+}
+
+fn results_changed(results: &[TestOutcome]) -> bool {
+    !results.windows(2).all(|w| w[0] == w[1])
 }
 
-fn print_row(html_url: &str, results: Vec<CompilationResult>) {
+fn print_row(html_url: &str, results: &[TestOutcome]) {
     use colored::*;
     use prettytable::{format, Cell, Row, Table};
 
-    let changed = !results.windows(2).all(|w| w[0] == w[1]);
+    let changed = results_changed(results);
     let url = if changed {
         html_url.blue()
     } else {
@@ -159,7 +446,7 @@ fn print_row(html_url: &str, results: Vec<CompilationResult>) {
     };
 
     let row = std::iter::once(format!("{:50}", url))
-        .chain(results.into_iter().map(|r| r.to_string()))
+        .chain(results.iter().map(|r| r.classification.to_string()))
         .map(|s| Cell::new(&s))
         .collect();
 
@@ -169,6 +456,27 @@ fn print_row(html_url: &str, results: Vec<CompilationResult>) {
     table.printstd();
 }
 
+fn print_normalized_diff(from_label: &str, to_label: &str, from: &str, to: &str) {
+    let diff = similar::TextDiff::from_lines(from, to)
+        .unified_diff()
+        .header(from_label, to_label)
+        .to_string();
+    print!("{}", diff);
+}
+
+fn print_toolchain_diffs(toolchains: &[String], results: &[TestOutcome]) {
+    for (index, window) in results.windows(2).enumerate() {
+        if window[0] != window[1] {
+            print_normalized_diff(
+                &toolchains[index],
+                &toolchains[index + 1],
+                &window[0].normalized_output,
+                &window[1].normalized_output,
+            );
+        }
+    }
+}
+
 fn print_headers() {
     use prettytable::{format, Cell, Row, Table};
 
@@ -187,47 +495,627 @@ fn print_headers() {
     table.printstd();
 }
 
+#[derive(Serialize)]
+struct ToolchainResult<'a> {
+    toolchain: &'a str,
+    result: CompilationResult,
+    panic_message: &'a Option<String>,
+    error_code: &'a Option<String>,
+}
+
+#[derive(Serialize)]
+struct IssueRecord<'a> {
+    html_url: &'a str,
+    mcve: &'a str,
+    results: Vec<ToolchainResult<'a>>,
+    changed: bool,
+}
+
+#[derive(Serialize)]
+struct SummaryRecord {
+    issues_without_mcve: Vec<String>,
+}
+
+fn build_issue_record<'a>(
+    toolchains: &'a [String],
+    html_url: &'a str,
+    mcve: &'a str,
+    results: &'a [TestOutcome],
+) -> IssueRecord<'a> {
+    IssueRecord {
+        html_url,
+        mcve,
+        results: toolchains
+            .iter()
+            .zip(results)
+            .map(|(toolchain, result)| ToolchainResult {
+                toolchain: toolchain.as_str(),
+                result: result.classification.clone(),
+                panic_message: &result.panic_message,
+                error_code: &result.error_code,
+            })
+            .collect(),
+        changed: results_changed(results),
+    }
+}
+
+fn print_json_row(html_url: &str, mcve: &str, results: &[TestOutcome]) {
+    let record = build_issue_record(TOOLCHAINS.as_slice(), html_url, mcve, results);
+    println!("{}", serde_json::to_string(&record).unwrap());
+}
+
+enum IssueOutcome {
+    WithMcve {
+        html_url: String,
+        mcve: String,
+        results: Vec<TestOutcome>,
+    },
+    WithoutMcve(Issue),
+}
+
+async fn run_mcve(mcve: &Mcve) -> Result<Vec<TestOutcome>, Error> {
+    use futures::stream::StreamExt;
+
+    let stream = TOOLCHAINS
+        .iter()
+        .enumerate()
+        .map(|(index, toolchain)| async move { (index, run_test(toolchain, mcve).await) })
+        .collect::<futures::stream::FuturesUnordered<_>>();
+
+    let mut results: Vec<(usize, Result<TestOutcome, Error>)> = stream.collect().await;
+    results.sort_by_key(|el| el.0);
+    results.into_iter().map(|el| el.1).collect()
+}
+
+fn worst_severity(results: &[TestOutcome]) -> u8 {
+    results
+        .iter()
+        .map(|r| r.classification.severity())
+        .max()
+        .unwrap_or(0)
+}
+
+async fn process_issue(issue: Issue) -> Result<IssueOutcome, Error> {
+    let mcves: Vec<Mcve> = get_mcves(&issue).collect();
+    if mcves.is_empty() {
+        return Ok(IssueOutcome::WithoutMcve(issue));
+    }
+
+    // ICE reproducers are frequently the second or third fenced block after
+    // a "here's the context" block, so every snippet is compiled and we
+    // report whichever one is the most interesting (ICE > Failed >
+    // Compiled) rather than just the first.
+    let mut worst: Option<(String, Vec<TestOutcome>)> = None;
+    for mcve in &mcves {
+        let results = run_mcve(mcve).await?;
+        let better = worst.as_ref().map_or(true, |(_, prev)| {
+            worst_severity(&results) > worst_severity(prev)
+        });
+        if better {
+            worst = Some((mcve.source.clone(), results));
+        }
+    }
+    let (mcve, results) = worst.expect("mcves is non-empty");
+
+    Ok(IssueOutcome::WithMcve {
+        html_url: issue.html_url,
+        mcve,
+        results,
+    })
+}
+
+type Snapshots = std::collections::HashMap<String, std::collections::HashMap<String, String>>;
+
+const SNAPSHOT_PATH: &str = "ices-scan-snapshots.json";
+
+fn load_snapshots() -> Snapshots {
+    std::fs::read_to_string(SNAPSHOT_PATH)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_snapshots(snapshots: &Snapshots) -> Result<(), Error> {
+    let contents =
+        serde_json::to_string_pretty(snapshots).expect("a Snapshots value is always serializable");
+    std::fs::write(SNAPSHOT_PATH, contents)?;
+    Ok(())
+}
+
+fn print_previous_scan_diffs(
+    previous: Option<&std::collections::HashMap<String, String>>,
+    toolchains: &[String],
+    results: &[TestOutcome],
+) {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return,
+    };
+
+    for (toolchain, result) in toolchains.iter().zip(results) {
+        if let Some(previous_output) = previous.get(toolchain) {
+            if *previous_output != result.normalized_output {
+                print_normalized_diff(
+                    "previous scan",
+                    toolchain,
+                    previous_output,
+                    &result.normalized_output,
+                );
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct WatchCacheEntry {
+    mcve: String,
+    classifications: Vec<CompilationResult>,
+}
+
+type WatchCache = std::collections::HashMap<String, WatchCacheEntry>;
+
+const WATCH_CACHE_PATH: &str = "ices-scan-watch-cache.json";
+
+fn load_watch_cache() -> WatchCache {
+    std::fs::read_to_string(WATCH_CACHE_PATH)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_watch_cache(cache: &WatchCache) -> Result<(), Error> {
+    let contents =
+        serde_json::to_string_pretty(cache).expect("a WatchCache value is always serializable");
+    std::fs::write(WATCH_CACHE_PATH, contents)?;
+    Ok(())
+}
+
+fn describe_classifications(
+    toolchains: &[String],
+    classifications: &[CompilationResult],
+) -> String {
+    toolchains
+        .iter()
+        .zip(classifications)
+        .map(|(toolchain, classification)| format!("{}={:?}", toolchain, classification))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn watch_transition(
+    previous: Option<&WatchCacheEntry>,
+    toolchains: &[String],
+    mcve: &str,
+    classifications: &[CompilationResult],
+) -> Option<String> {
+    match previous {
+        None => Some("new".to_owned()),
+        Some(prev) if prev.mcve != mcve => Some("MCVE edited".to_owned()),
+        Some(prev) if prev.classifications != classifications => Some(format!(
+            "{} -> {}",
+            describe_classifications(toolchains, &prev.classifications),
+            describe_classifications(toolchains, classifications)
+        )),
+        Some(_) => None,
+    }
+}
+
+async fn scan_issues(etag_cache: &mut EtagCache) -> Result<Vec<IssueOutcome>, Error> {
+    use futures::stream::StreamExt;
+
+    let mut pending: BTreeMap<u64, IssueOutcome> = BTreeMap::new();
+    let mut next_to_print: u64 = 0;
+    let mut outcomes = Vec::new();
+
+    let results = Box::pin(get_issues(etag_cache))
+        .enumerate()
+        .map(|(index, issue)| async move {
+            match issue {
+                Ok(issue) => (index as u64, process_issue(issue).await),
+                Err(err) => (index as u64, Err(err)),
+            }
+        });
+    let mut results = results.buffer_unordered(*ISSUE_CONCURRENCY);
+
+    while let Some((index, outcome)) = results.next().await {
+        pending.insert(index, outcome?);
+        while let Some(outcome) = pending.remove(&next_to_print) {
+            outcomes.push(outcome);
+            next_to_print += 1;
+        }
+    }
+
+    Ok(outcomes)
+}
+
+async fn watch_loop(interval: std::time::Duration) -> ! {
+    loop {
+        let previous = load_watch_cache();
+        let mut etag_cache = load_etag_cache();
+
+        let outcomes = scan_issues(&mut etag_cache).await;
+        // Persist whatever ETags we did pick up even on a failed poll, so a
+        // later page that did return before the error isn't re-fetched.
+        save_etag_cache(&etag_cache).expect("failed to persist the ETag cache");
+
+        let outcomes = match outcomes {
+            Ok(outcomes) => outcomes,
+            Err(err) => {
+                eprintln!("watch: poll failed, retrying in {:?}: {:?}", interval, err);
+                tokio::time::sleep(interval).await;
+                continue;
+            }
+        };
+
+        let mut cache = WatchCache::new();
+        for outcome in outcomes {
+            let (html_url, mcve, results) = match outcome {
+                IssueOutcome::WithMcve {
+                    html_url,
+                    mcve,
+                    results,
+                } => (html_url, mcve, results),
+                IssueOutcome::WithoutMcve(_) => continue,
+            };
+
+            let classifications: Vec<CompilationResult> =
+                results.iter().map(|r| r.classification.clone()).collect();
+
+            let transition = watch_transition(
+                previous.get(&html_url),
+                TOOLCHAINS.as_slice(),
+                &mcve,
+                &classifications,
+            );
+
+            if let Some(transition) = transition {
+                println!("{}: {}", html_url, transition);
+            }
+
+            cache.insert(
+                html_url,
+                WatchCacheEntry {
+                    mcve,
+                    classifications,
+                },
+            );
+        }
+
+        save_watch_cache(&cache).expect("failed to persist the watch cache");
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
 #[tokio::main]
 async fn main() {
+    if let Some(interval) = ARGS.watch {
+        watch_loop(std::time::Duration::from_secs(interval)).await;
+    }
+
     use futures::stream::StreamExt;
 
-    let mut i: u16 = 0;
+    let progress = indicatif::ProgressBar::new_spinner();
+    progress.set_style(
+        indicatif::ProgressStyle::default_spinner()
+            .template("{spinner} {pos} issues scanned ({per_sec})"),
+    );
 
+    let mut i: u16 = 0;
     let mut issues_without_mcve = vec![];
 
-    #[for_await]
-    for issue in get_issues() {
-        let issue = issue.unwrap();
-        let mcve = get_mcves(&issue).next();
+    let previous_snapshots = load_snapshots();
+    let mut snapshots = Snapshots::new();
 
-        if let Some(mcve) = mcve {
-            let stream = TOOLCHAINS
-                .iter()
-                .enumerate()
-                .map(|(index, toolchain)| {
-                    let mcve = &mcve;
-                    async move { (index, run_test(toolchain, mcve).await) }
-                })
-                .collect::<futures::stream::FuturesUnordered<_>>();
+    // Issues compile concurrently, bounded by ISSUE_CONCURRENCY in-flight at
+    // once, but buffer_unordered() completes them out of order, so we stash
+    // finished outcomes here and only print once every lower-indexed issue
+    // has printed, keeping the table in the same order as get_issues().
+    let mut pending: BTreeMap<u64, Result<IssueOutcome, Error>> = BTreeMap::new();
+    let mut next_to_print: u64 = 0;
+
+    let mut etag_cache = EtagCache::new();
+    let results =
+        Box::pin(get_issues(&mut etag_cache))
+            .enumerate()
+            .map(|(index, issue)| async move {
+                match issue {
+                    Ok(issue) => (index as u64, process_issue(issue).await),
+                    Err(err) => (index as u64, Err(err)),
+                }
+            });
+    let mut results = results.buffer_unordered(*ISSUE_CONCURRENCY);
+
+    while let Some((index, outcome)) = results.next().await {
+        progress.inc(1);
+        pending.insert(index, outcome);
+
+        while let Some(outcome) = pending.remove(&next_to_print) {
+            match outcome {
+                Ok(IssueOutcome::WithMcve {
+                    html_url,
+                    mcve,
+                    results,
+                }) => {
+                    match ARGS.output {
+                        OutputMode::Table => {
+                            if i % 10 == 0 {
+                                print_headers();
+                            }
+                            print_row(&html_url, &results);
+                            print_toolchain_diffs(TOOLCHAINS.as_slice(), &results);
+                            print_previous_scan_diffs(
+                                previous_snapshots.get(&html_url),
+                                TOOLCHAINS.as_slice(),
+                                &results,
+                            );
+                        }
+                        OutputMode::Json => print_json_row(&html_url, &mcve, &results),
+                    }
+
+                    let toolchain_outputs = TOOLCHAINS
+                        .iter()
+                        .cloned()
+                        .zip(results.iter().map(|r| r.normalized_output.clone()))
+                        .collect();
+                    snapshots.insert(html_url, toolchain_outputs);
+
+                    i += 1;
+                }
+                Ok(IssueOutcome::WithoutMcve(issue)) => issues_without_mcve.push(issue),
+                Err(err) => eprintln!("error processing issue: {}", err),
+            }
+            next_to_print += 1;
+        }
+    }
+
+    progress.finish_and_clear();
 
-            let mut results: Vec<(usize, Result<CompilationResult, Error>)> =
-                stream.collect().await;
-            results.sort_by_key(|el| el.0);
-            let results = results.into_iter().map(|el| el.1.unwrap()).collect();
+    save_snapshots(&snapshots).expect("failed to persist scan snapshots");
 
-            if i % 10 == 0 {
-                print_headers();
+    match ARGS.output {
+        OutputMode::Table => {
+            println!();
+            println!("Issues without MCVEs:");
+            for issue in issues_without_mcve {
+                println!("{}", issue.html_url);
             }
-            print_row(&issue.html_url, results);
-            i += 1;
-        } else {
-            issues_without_mcve.push(issue);
         }
+        OutputMode::Json => {
+            let summary = SummaryRecord {
+                issues_without_mcve: issues_without_mcve
+                    .into_iter()
+                    .map(|issue| issue.html_url)
+                    .collect(),
+            };
+            println!("{}", serde_json::to_string(&summary).unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_directives_extracts_flags_and_edition() {
+        let source = "// compile-flags: -Zpolonius --crate-type=lib\n// edition:2021\nfn main() {}";
+        let (flags, edition) = parse_directives(source);
+        assert_eq!(flags, vec!["-Zpolonius", "--crate-type=lib"]);
+        assert_eq!(edition.as_deref(), Some("2021"));
+    }
+
+    #[test]
+    fn parse_directives_defaults_when_absent() {
+        let (flags, edition) = parse_directives("fn main() {}");
+        assert!(flags.is_empty());
+        assert_eq!(edition, None);
+    }
+
+    #[test]
+    fn normalize_rustc_output_strips_paths_and_line_col() {
+        let source_path = std::path::Path::new("/tmp/ices-scan-abc123.rs");
+        let artifact_path = std::path::Path::new("/tmp/ices-scan-abc123-out");
+        let raw = format!(
+            "error: expected expression\n --> {}:3:5\n\nsome note referencing {}",
+            source_path.display(),
+            artifact_path.display()
+        );
+        let normalized = normalize_rustc_output(&raw, source_path, artifact_path);
+        assert!(normalized.contains("$DIR"));
+        assert!(normalized.contains("$OUT"));
+        assert!(!normalized.contains(":3:5"));
+    }
+
+    #[test]
+    fn normalize_rustc_output_collapses_backtrace() {
+        let source_path = std::path::Path::new("/tmp/in.rs");
+        let artifact_path = std::path::Path::new("/tmp/out");
+        let raw = "thread 'rustc' panicked at 'oops', src/foo.rs:1:1\nstack backtrace:\n   0: foo\n   1: bar\n";
+        let normalized = normalize_rustc_output(raw, source_path, artifact_path);
+        assert!(normalized.contains("stack backtrace: (omitted)"));
+        assert!(!normalized.contains("0: foo"));
+    }
+
+    #[test]
+    fn extract_panic_message_matches_current_format() {
+        let normalized =
+            "thread 'rustc' (12345) panicked at $DIR/foo.rs:\nindex out of bounds: the len is 0 but the index is 1\n";
+        assert_eq!(
+            extract_panic_message(normalized).as_deref(),
+            Some("index out of bounds: the len is 0 but the index is 1")
+        );
+    }
+
+    #[test]
+    fn extract_panic_message_falls_back_to_legacy_format() {
+        let normalized = "thread 'rustc' panicked at 'explicit panic', src/foo.rs";
+        assert_eq!(
+            extract_panic_message(normalized).as_deref(),
+            Some("explicit panic")
+        );
+    }
+
+    #[test]
+    fn extract_panic_message_none_when_absent() {
+        assert_eq!(
+            extract_panic_message("error[E0308]: mismatched types"),
+            None
+        );
+    }
+
+    #[test]
+    fn extract_error_code_finds_first_code() {
+        let normalized = "error[E0308]: mismatched types\nerror[E0277]: trait bound not satisfied";
+        assert_eq!(extract_error_code(normalized).as_deref(), Some("E0308"));
+    }
+
+    #[test]
+    fn extract_error_code_none_when_absent() {
+        assert_eq!(extract_error_code("error: expected expression"), None);
+    }
+
+    fn outcome(classification: CompilationResult) -> TestOutcome {
+        TestOutcome {
+            classification,
+            panic_message: None,
+            error_code: None,
+            normalized_output: String::new(),
+        }
+    }
+
+    #[test]
+    fn worst_severity_picks_the_most_interesting_result() {
+        let results = vec![
+            outcome(CompilationResult::Compiled),
+            outcome(CompilationResult::ICE),
+            outcome(CompilationResult::Failed),
+        ];
+        assert_eq!(worst_severity(&results), CompilationResult::ICE.severity());
+    }
+
+    #[test]
+    fn worst_severity_empty_is_zero() {
+        assert_eq!(worst_severity(&[]), 0);
+    }
+
+    #[test]
+    fn results_changed_true_when_toolchains_disagree() {
+        let results = vec![
+            outcome(CompilationResult::Compiled),
+            outcome(CompilationResult::ICE),
+        ];
+        assert!(results_changed(&results));
+    }
+
+    #[test]
+    fn results_changed_false_when_all_toolchains_agree() {
+        let results = vec![
+            outcome(CompilationResult::ICE),
+            outcome(CompilationResult::ICE),
+        ];
+        assert!(!results_changed(&results));
+    }
+
+    #[test]
+    fn build_issue_record_sets_changed_when_toolchains_disagree() {
+        let toolchains = vec!["nightly-1".to_owned(), "nightly-2".to_owned()];
+        let results = vec![
+            outcome(CompilationResult::Compiled),
+            outcome(CompilationResult::ICE),
+        ];
+        let record = build_issue_record(&toolchains, "https://example/1", "fn main() {}", &results);
+        assert!(record.changed);
+        assert_eq!(record.results.len(), 2);
+        assert_eq!(record.results[0].toolchain, "nightly-1");
+        assert_eq!(record.results[1].result, CompilationResult::ICE);
+    }
+
+    #[test]
+    fn build_issue_record_not_changed_when_toolchains_agree() {
+        let toolchains = vec!["nightly-1".to_owned(), "nightly-2".to_owned()];
+        let results = vec![
+            outcome(CompilationResult::ICE),
+            outcome(CompilationResult::ICE),
+        ];
+        let record = build_issue_record(&toolchains, "https://example/2", "fn main() {}", &results);
+        assert!(!record.changed);
+    }
+
+    #[test]
+    fn describe_classifications_joins_toolchain_and_result() {
+        let toolchains = vec!["nightly-1".to_owned(), "nightly-2".to_owned()];
+        let classifications = vec![CompilationResult::Compiled, CompilationResult::ICE];
+        assert_eq!(
+            describe_classifications(&toolchains, &classifications),
+            "nightly-1=Compiled, nightly-2=ICE"
+        );
+    }
+
+    #[test]
+    fn watch_transition_new_when_no_previous_entry() {
+        let toolchains = vec!["nightly-1".to_owned()];
+        let classifications = vec![CompilationResult::ICE];
+        assert_eq!(
+            watch_transition(None, &toolchains, "fn main() {}", &classifications),
+            Some("new".to_owned())
+        );
+    }
+
+    #[test]
+    fn watch_transition_mcve_edited_when_source_changed() {
+        let toolchains = vec!["nightly-1".to_owned()];
+        let classifications = vec![CompilationResult::ICE];
+        let previous = WatchCacheEntry {
+            mcve: "fn old() {}".to_owned(),
+            classifications: classifications.clone(),
+        };
+        assert_eq!(
+            watch_transition(
+                Some(&previous),
+                &toolchains,
+                "fn new() {}",
+                &classifications
+            ),
+            Some("MCVE edited".to_owned())
+        );
+    }
+
+    #[test]
+    fn watch_transition_describes_classification_change() {
+        let toolchains = vec!["nightly-1".to_owned()];
+        let previous = WatchCacheEntry {
+            mcve: "fn main() {}".to_owned(),
+            classifications: vec![CompilationResult::Compiled],
+        };
+        let classifications = vec![CompilationResult::ICE];
+        assert_eq!(
+            watch_transition(
+                Some(&previous),
+                &toolchains,
+                "fn main() {}",
+                &classifications
+            ),
+            Some("nightly-1=Compiled -> nightly-1=ICE".to_owned())
+        );
     }
 
-    println!();
-    println!("Issues without MCVEs:");
-    for issue in issues_without_mcve {
-        println!("{}", issue.html_url);
+    #[test]
+    fn watch_transition_none_when_unchanged() {
+        let toolchains = vec!["nightly-1".to_owned()];
+        let classifications = vec![CompilationResult::ICE];
+        let previous = WatchCacheEntry {
+            mcve: "fn main() {}".to_owned(),
+            classifications: classifications.clone(),
+        };
+        assert_eq!(
+            watch_transition(
+                Some(&previous),
+                &toolchains,
+                "fn main() {}",
+                &classifications
+            ),
+            None
+        );
     }
 }